@@ -3,14 +3,58 @@
 //! This module handles parsing command-line arguments, environment variables,
 //! and creating a unified configuration structure for the application.
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 use std::env;
-use std::path::PathBuf;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
 
 use crate::Args;
+use crate::container::BuildProgress;
 use crate::dockerfile::DockerfileLocator;
 use crate::engine::EngineType;
 use crate::lockfile::{DockerfileInfo, Lockfile};
+use crate::naming;
+use crate::shell;
+
+/// How much the tool reports about what it is doing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default)]
+pub enum Verbosity {
+    /// Only errors (on stderr) and explicitly requested output
+    Quiet,
+    /// Status messages such as "Building image"
+    #[default]
+    Normal,
+    /// Status messages plus every engine command as it runs
+    Verbose,
+}
+
+impl Verbosity {
+    /// Determines the verbosity from the `-q` and `-v` flags
+    pub fn from_flags(quiet: bool, verbose: u8) -> Self {
+        if quiet {
+            Verbosity::Quiet
+        } else if verbose > 0 {
+            Verbosity::Verbose
+        } else {
+            Verbosity::Normal
+        }
+    }
+
+    /// Prints a status message unless running quietly
+    pub fn info(&self, message: impl fmt::Display) {
+        if *self > Verbosity::Quiet {
+            println!("{}", message);
+        }
+    }
+
+    /// Echoes an engine command to stderr when running verbosely
+    pub fn trace(&self, cmd: &Command) {
+        if *self >= Verbosity::Verbose {
+            eprintln!("+ {}", shell::command_line(cmd));
+        }
+    }
+}
 
 /// Application configuration structure
 ///
@@ -29,25 +73,46 @@ pub struct Config {
     pub engine_type: EngineType,
     /// Whether to force rebuild the image and recreate the container
     pub update_image: bool,
+    /// Progress output style for image builds
+    pub build_progress: BuildProgress,
+    /// Number of times to retry a build that failed with a network error
+    pub build_retries: u32,
     /// Custom command to run in the container (empty means use default shell)
     pub custom_command: Vec<String>,
+    /// Whether to print engine commands instead of running them
+    pub dry_run: bool,
+    /// How much to report while running
+    pub verbosity: Verbosity,
+    /// Whether to print how long the build and run took
+    pub time: bool,
+    /// Script to write a standalone run command to instead of running anything
+    pub emit_script: Option<PathBuf>,
     /// Lockfile for tracking Dockerfile state
     pub lockfile: Lockfile,
     /// Host user's UID for container user mapping
     pub user_uid: u32,
     /// Host user's GID for container user mapping
     pub user_gid: u32,
+    /// UID passed to the image build as the `UID` build argument
+    pub build_uid: u32,
+    /// GID passed to the image build as the `GID` build argument
+    pub build_gid: u32,
 }
 
 impl Config {
+    /// UID/GID used when building a generic image with `--generic-image`
+    const GENERIC_BUILD_ID: u32 = 1000;
+
     /// Creates a new configuration from command-line arguments and environment variables
     ///
     /// This method combines CLI arguments with environment variable defaults to create
     /// a complete configuration. It handles:
     /// - Dockerfile location detection (CLI arg > env var > automatic search > fallback)
+    /// - Symlink resolution of the Dockerfile path when `--follow-symlinks` is set
     /// - Container name generation based on Dockerfile location
+    /// - Placeholder expansion in container names (`{branch}`, `{dir}`, `{user}`, `{hash}`)
     /// - Image name generation based on Dockerfile location
-    /// - Container engine selection (env var or default to podman)
+    /// - Container engine selection (env var, or the first of docker/podman found in PATH)
     ///
     /// # Arguments
     ///
@@ -59,31 +124,26 @@ impl Config {
     ///
     /// # Environment Variables
     ///
-    /// * `CONTAINER_ENGINE` - Container engine to use (docker/podman, defaults to podman)
+    /// * `CONTAINER_ENGINE` - Container engine to use (docker/podman/nerdctl/apptainer, default: auto-detect docker, then podman)
     /// * `DOCKERFILE` - Path to Dockerfile (overridden by CLI arg)
     /// * `CONTAINER_NAME` - Container name (overridden by CLI arg)
+    /// * `BUILD_UID` / `BUILD_GID` - UID/GID build arguments (default: current user)
     pub fn from_args_and_env(args: Args) -> Result<Self> {
-        let engine_type = env::var("CONTAINER_ENGINE")
-            .unwrap_or_else(|_| "podman".to_string())
-            .parse::<EngineType>()
-            .unwrap_or_default();
+        let engine_type = Self::engine_type_from_env()?;
 
         // Find Dockerfile
-        let dockerfile = if let Some(dockerfile) = args.dockerfile {
-            dockerfile
-        } else if let Ok(dockerfile) = env::var("DOCKERFILE") {
-            PathBuf::from(dockerfile)
-        } else {
-            DockerfileLocator::find().ok_or_else(|| {
-                anyhow::anyhow!(
-                    "No Dockerfile found. Searched from current directory up to home directory.\n\
+        let dockerfile = Self::locate_dockerfile(args.dockerfile).ok_or_else(|| {
+            anyhow::anyhow!(
+                "No Dockerfile found. Searched from current directory up to home directory.\n\
                      You can specify a Dockerfile with:\n\
                      - The -f/--dockerfile flag\n\
                      - The DOCKERFILE environment variable\n\
                      - Or create a Dockerfile in the current directory or any parent directory"
-                )
-            })?
-        };
+            )
+        })?;
+
+        // Resolve symlinks so the mount directory and lockfile follow the real file
+        let dockerfile = Self::resolve_dockerfile(&dockerfile, args.follow_symlinks)?;
 
         // Load or create lockfile to get the content hash
         let lockfile = Lockfile::load_or_create(&dockerfile)?;
@@ -102,24 +162,229 @@ impl Config {
         } else {
             env::var("CONTAINER_NAME").unwrap_or(default_container_name)
         };
-
-        // Generate image name based on hash
-        let image_name = format!("{}:latest", hash_prefix);
+        let container_name = naming::expand_container_name(&container_name, hash_prefix)?;
 
         // Get current user's UID and GID for container user mapping
         let user_uid = users::get_current_uid();
         let user_gid = users::get_current_gid();
 
+        let (build_uid, build_gid) = Self::build_ids(args.generic_image, user_uid, user_gid)?;
+
+        // Generate image name based on hash; Apptainer images are SIF files next to the Dockerfile
+        let image_name = if engine_type == EngineType::Apptainer {
+            dockerfile
+                .with_file_name(format!("{}.sif", hash_prefix))
+                .display()
+                .to_string()
+        } else {
+            Self::image_name(hash_prefix, build_uid, build_gid)
+        };
+
         Ok(Self {
             dockerfile,
             container_name,
             image_name,
             engine_type,
             update_image: args.update,
+            build_progress: args.progress.unwrap_or_else(BuildProgress::detect),
+            build_retries: args.build_retries,
             custom_command: args.command,
+            dry_run: args.dry_run,
+            verbosity: Verbosity::from_flags(args.quiet, args.verbose),
+            time: args.time,
+            emit_script: args.emit_script,
             lockfile,
             user_uid,
             user_gid,
+            build_uid,
+            build_gid,
+        })
+    }
+
+    /// Determines the container engine from the `CONTAINER_ENGINE` environment variable
+    ///
+    /// When the variable is unset, the first installed engine among docker and
+    /// podman is used.
+    ///
+    /// # Returns
+    ///
+    /// The requested engine (podman if the value is unrecognized), the detected
+    /// engine, or an error if the variable is unset and no engine is installed.
+    pub fn engine_type_from_env() -> Result<EngineType> {
+        match env::var("CONTAINER_ENGINE") {
+            Ok(engine) => Ok(engine.parse::<EngineType>().unwrap_or_default()),
+            Err(_) => Ok(EngineType::detect()?),
+        }
+    }
+
+    /// Determines the UID/GID the image is built for
+    ///
+    /// A generic image uses a fixed, well-known ID. Otherwise `BUILD_UID` and
+    /// `BUILD_GID` override the current user's IDs.
+    ///
+    /// # Arguments
+    ///
+    /// * `generic_image` - Whether `--generic-image` was given
+    /// * `user_uid` - The current user's UID
+    /// * `user_gid` - The current user's GID
+    ///
+    /// # Returns
+    ///
+    /// Returns the build UID and GID, or an error if an override is not a valid ID.
+    fn build_ids(generic_image: bool, user_uid: u32, user_gid: u32) -> Result<(u32, u32)> {
+        if generic_image {
+            return Ok((Self::GENERIC_BUILD_ID, Self::GENERIC_BUILD_ID));
+        }
+        Ok((
+            Self::id_from_env("BUILD_UID")?.unwrap_or(user_uid),
+            Self::id_from_env("BUILD_GID")?.unwrap_or(user_gid),
+        ))
+    }
+
+    /// Generates the image name for a Dockerfile hash and build IDs
+    ///
+    /// The build IDs are part of the tag, so images built for different users
+    /// coexist and changing them selects (or builds) a different image.
+    fn image_name(hash_prefix: &str, build_uid: u32, build_gid: u32) -> String {
+        format!("{}:u{}-g{}", hash_prefix, build_uid, build_gid)
+    }
+
+    /// Returns the name this Dockerfile's image had before build IDs were part of the tag
+    ///
+    /// Containers created from such an image are kept rather than recreated,
+    /// so upgrading does not wipe their state.
+    pub fn legacy_image_name(&self) -> Option<String> {
+        if self.engine_type == EngineType::Apptainer {
+            return None;
+        }
+        let (repository, _) = self.image_name.split_once(':')?;
+        Some(format!("{}:latest", repository))
+    }
+
+    /// Reads a numeric user or group ID from an environment variable
+    ///
+    /// # Returns
+    ///
+    /// Returns `Ok(None)` if the variable is unset, or an error if it is set
+    /// but not a valid ID.
+    fn id_from_env(name: &str) -> Result<Option<u32>> {
+        match env::var(name) {
+            Ok(value) => value
+                .trim()
+                .parse::<u32>()
+                .map(Some)
+                .with_context(|| format!("Invalid {}: {}", name, value)),
+            Err(_) => Ok(None),
+        }
+    }
+
+    /// Resolves symlinks in the Dockerfile path when `--follow-symlinks` is set
+    ///
+    /// # Arguments
+    ///
+    /// * `dockerfile` - The located Dockerfile path
+    /// * `follow_symlinks` - Whether to resolve the path to the real file
+    ///
+    /// # Returns
+    ///
+    /// Returns the path to use as the lockfile key and mount source, or an
+    /// error if symlinks were requested but the path cannot be resolved.
+    pub fn resolve_dockerfile(dockerfile: &Path, follow_symlinks: bool) -> Result<PathBuf> {
+        if follow_symlinks {
+            DockerfileLocator::resolve_symlinks(dockerfile)
+        } else {
+            Ok(dockerfile.to_path_buf())
+        }
+    }
+
+    /// Locates and resolves the Dockerfile for `--doctor`
+    ///
+    /// Resolves the path like a normal run, so the lockfile is looked up under
+    /// the same key a build records. A missing file cannot be resolved and is
+    /// returned unresolved for the doctor to report.
+    ///
+    /// # Arguments
+    ///
+    /// * `cli_dockerfile` - Dockerfile path passed on the command line, if any
+    /// * `follow_symlinks` - Whether `--follow-symlinks` was given
+    ///
+    /// # Returns
+    ///
+    /// Returns the Dockerfile path, or `None` if none was given and the search found nothing.
+    pub fn doctor_dockerfile(
+        cli_dockerfile: Option<PathBuf>,
+        follow_symlinks: bool,
+    ) -> Option<PathBuf> {
+        Self::locate_dockerfile(cli_dockerfile).map(|dockerfile| {
+            Self::resolve_dockerfile(&dockerfile, follow_symlinks).unwrap_or(dockerfile)
         })
     }
+
+    /// Locates the Dockerfile to use
+    ///
+    /// Priority: CLI argument > `DOCKERFILE` environment variable > upward search.
+    ///
+    /// # Arguments
+    ///
+    /// * `cli_dockerfile` - Dockerfile path passed on the command line, if any
+    ///
+    /// # Returns
+    ///
+    /// Returns `Some(PathBuf)` with the Dockerfile path, or `None` if none was
+    /// given and the search found nothing.
+    pub fn locate_dockerfile(cli_dockerfile: Option<PathBuf>) -> Option<PathBuf> {
+        if let Some(dockerfile) = cli_dockerfile {
+            Some(dockerfile)
+        } else if let Ok(dockerfile) = env::var("DOCKERFILE") {
+            Some(PathBuf::from(dockerfile))
+        } else {
+            DockerfileLocator::find()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_verbosity_from_flags() {
+        assert_eq!(Verbosity::from_flags(false, 0), Verbosity::Normal);
+        assert_eq!(Verbosity::from_flags(false, 2), Verbosity::Verbose);
+        assert_eq!(Verbosity::from_flags(true, 0), Verbosity::Quiet);
+    }
+
+    #[test]
+    fn test_id_from_env() {
+        // Variable names are unique to this test, so parallel tests cannot race on them
+        let name = "CONTAINERS_TEST_ID_FROM_ENV";
+        unsafe { env::remove_var(name) };
+        assert_eq!(Config::id_from_env(name).unwrap(), None);
+
+        unsafe { env::set_var(name, " 1001 ") };
+        assert_eq!(Config::id_from_env(name).unwrap(), Some(1001));
+
+        for invalid in ["abc", "-1", "", "4294967296"] {
+            unsafe { env::set_var(name, invalid) };
+            assert!(Config::id_from_env(name).is_err(), "accepted {:?}", invalid);
+        }
+        unsafe { env::remove_var(name) };
+    }
+
+    #[test]
+    fn test_generic_image_build_ids() {
+        assert_eq!(Config::build_ids(true, 1234, 5678).unwrap(), (1000, 1000));
+    }
+
+    #[test]
+    fn test_image_name_includes_build_ids() {
+        assert_eq!(
+            Config::image_name("a1b2c3d4e5f6", 1000, 1000),
+            "a1b2c3d4e5f6:u1000-g1000"
+        );
+        assert_ne!(
+            Config::image_name("a1b2c3d4e5f6", 1000, 1000),
+            Config::image_name("a1b2c3d4e5f6", 1001, 1000)
+        );
+    }
 }
@@ -5,22 +5,89 @@
 //! common operations for container lifecycle management.
 
 use anyhow::{Context, Result};
+use std::fmt;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Write};
 use std::path::Path;
-use std::process::{Command, Stdio};
+use std::process::{Command, ExitStatus, Output, Stdio};
+use std::str::FromStr;
+use std::thread;
 
+use crate::config::Verbosity;
 use crate::engine::EngineType;
 use crate::errors::ContainerError;
+use crate::shell;
+
+/// Progress output style for image builds
+///
+/// Maps to the `--progress` option of `docker build` and `nerdctl build`.
+/// Podman's build output is always plain, so the setting has no effect there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BuildProgress {
+    /// Let the builder decide based on the terminal
+    Auto,
+    /// Plain line-based output, suitable for CI logs
+    Plain,
+    /// Interactive output that redraws in place
+    Tty,
+}
+
+impl BuildProgress {
+    /// Returns the value passed to `--progress`
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BuildProgress::Auto => "auto",
+            BuildProgress::Plain => "plain",
+            BuildProgress::Tty => "tty",
+        }
+    }
+
+    /// Picks plain output when stdout is not a terminal, auto otherwise
+    pub fn detect() -> Self {
+        if io::stdout().is_terminal() {
+            BuildProgress::Auto
+        } else {
+            BuildProgress::Plain
+        }
+    }
+}
+
+impl fmt::Display for BuildProgress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.as_str())
+    }
+}
+
+impl FromStr for BuildProgress {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "auto" => Ok(BuildProgress::Auto),
+            "plain" => Ok(BuildProgress::Plain),
+            "tty" => Ok(BuildProgress::Tty),
+            _ => Err(format!(
+                "Unknown progress mode: {} (expected plain, tty or auto)",
+                s
+            )),
+        }
+    }
+}
 
 /// Container engine abstraction
 ///
 /// Provides a unified interface for container operations that works with
-/// both Docker and Podman. Automatically detects NVIDIA GPU support and
-/// handles engine-specific argument differences.
+/// Docker, Podman, nerdctl and, with reduced functionality, Apptainer.
+/// Automatically detects NVIDIA GPU support and handles engine-specific
+/// argument differences.
 pub struct ContainerEngine {
-    /// The container engine type (docker or podman)
+    /// The container engine type
     engine_type: EngineType,
     /// NVIDIA GPU support arguments for this engine
     nvidia_args: Vec<String>,
+    /// Whether to print state-changing commands instead of running them
+    dry_run: bool,
+    /// How much to report while running
+    verbosity: Verbosity,
 }
 
 impl ContainerEngine {
@@ -32,6 +99,9 @@ impl ContainerEngine {
     /// # Arguments
     ///
     /// * `engine_type` - The container engine to use
+    /// * `dry_run` - Print commands that would change state instead of running them;
+    ///   read-only queries such as `image_exists` still run
+    /// * `verbosity` - Whether to report progress and echo engine commands
     ///
     /// # Returns
     ///
@@ -41,7 +111,7 @@ impl ContainerEngine {
     ///
     /// Will return an error if the specified container engine is not installed
     /// or not accessible in the system PATH.
-    pub fn new(engine_type: EngineType) -> Result<Self> {
+    pub fn new(engine_type: EngineType, dry_run: bool, verbosity: Verbosity) -> Result<Self> {
         // Verify engine exists
         let command = engine_type.as_command();
         which::which(command)
@@ -52,9 +122,38 @@ impl ContainerEngine {
         Ok(Self {
             engine_type,
             nvidia_args,
+            dry_run,
+            verbosity,
         })
     }
 
+    /// Creates an engine without checking for the binary or GPU support
+    #[cfg(test)]
+    pub fn stub(engine_type: EngineType, dry_run: bool) -> Self {
+        Self {
+            engine_type,
+            nvidia_args: Vec::new(),
+            dry_run,
+            verbosity: Verbosity::Quiet,
+        }
+    }
+
+    /// Runs a command to completion, or only prints it in dry-run mode
+    fn status(&self, cmd: &mut Command) -> io::Result<ExitStatus> {
+        if self.dry_run {
+            println!("{}", shell::command_line(cmd));
+            return Ok(ExitStatus::default());
+        }
+        self.verbosity.trace(cmd);
+        cmd.status()
+    }
+
+    /// Runs a read-only query command and collects its output
+    fn output(&self, cmd: &mut Command) -> io::Result<Output> {
+        self.verbosity.trace(cmd);
+        cmd.output()
+    }
+
     /// Detects NVIDIA GPU support and returns appropriate arguments
     ///
     /// Checks if nvidia-smi is available and working, then returns the
@@ -72,25 +171,26 @@ impl ContainerEngine {
         let mut args = Vec::new();
 
         // Check if nvidia-smi exists and works
-        if which::which("nvidia-smi").is_ok() {
-            if let Ok(status) = Command::new("nvidia-smi")
+        if which::which("nvidia-smi").is_ok()
+            && let Ok(status) = Command::new("nvidia-smi")
                 .stdout(Stdio::null())
                 .stderr(Stdio::null())
                 .status()
-            {
-                if status.success() {
-                    match engine_type {
-                        EngineType::Docker => {
-                            args.push("--gpus".to_string());
-                            args.push("all".to_string());
-                        }
-                        EngineType::Podman => {
-                            args.push("--device".to_string());
-                            args.push("nvidia.com/gpu=all".to_string());
-                            args.push("--security-opt".to_string());
-                            args.push("label=disable".to_string());
-                        }
-                    }
+            && status.success()
+        {
+            match engine_type {
+                EngineType::Docker | EngineType::Nerdctl => {
+                    args.push("--gpus".to_string());
+                    args.push("all".to_string());
+                }
+                EngineType::Podman => {
+                    args.push("--device".to_string());
+                    args.push("nvidia.com/gpu=all".to_string());
+                    args.push("--security-opt".to_string());
+                    args.push("label=disable".to_string());
+                }
+                EngineType::Apptainer => {
+                    args.push("--nv".to_string());
                 }
             }
         }
@@ -98,8 +198,27 @@ impl ContainerEngine {
         args
     }
 
+    /// Returns whether this engine can build images from a Dockerfile
+    ///
+    /// Apptainer cannot; its SIF images must be created outside this tool.
+    pub fn can_build(&self) -> bool {
+        self.engine_type != EngineType::Apptainer
+    }
+
+    /// Builds the error returned for operations Apptainer has no equivalent for
+    fn unsupported(&self, operation: impl Into<String>) -> anyhow::Error {
+        ContainerError::Unsupported {
+            engine: self.engine_type.to_string(),
+            operation: operation.into(),
+        }
+        .into()
+    }
+
     /// Checks if a container image exists locally
     ///
+    /// With Apptainer the image is a SIF file, so this checks that the file
+    /// exists.
+    ///
     /// # Arguments
     ///
     /// * `image_name` - The name of the image to check for
@@ -109,17 +228,59 @@ impl ContainerEngine {
     /// Returns `Ok(true)` if the image exists, `Ok(false)` if it doesn't,
     /// or an error if the check fails.
     pub fn image_exists(&self, image_name: &str) -> Result<bool> {
-        let output = Command::new(self.engine_type.as_command())
-            .arg("images")
+        if self.engine_type == EngineType::Apptainer {
+            return Ok(Path::new(image_name).exists());
+        }
+
+        let mut cmd = Command::new(self.engine_type.as_command());
+        cmd.arg("images")
+            .arg("--format")
+            .arg("{{.Repository}}:{{.Tag}}");
+
+        let output = self.output(&mut cmd).context("Failed to list images")?;
+
+        Ok(image_listed(
+            &String::from_utf8_lossy(&output.stdout),
+            image_name,
+        ))
+    }
+
+    /// Returns the content-addressable ID of a local image
+    ///
+    /// Unlike the image tag, this ID identifies the exact built content and
+    /// can be used to pin downstream tooling to it.
+    ///
+    /// # Arguments
+    ///
+    /// * `image_name` - The name of the image to inspect
+    ///
+    /// # Returns
+    ///
+    /// Returns the image ID in `sha256:<hex>` form, or an error if the
+    /// inspection fails.
+    pub fn image_digest(&self, image_name: &str) -> Result<String> {
+        let mut cmd = Command::new(self.engine_type.as_command());
+        cmd.arg("image")
+            .arg("inspect")
             .arg("--format")
-            .arg("table {{.Repository}}:{{.Tag}}")
-            .output()
-            .context("Failed to list images")?;
+            .arg("{{.Id}}")
+            .arg(image_name);
+
+        let output = self.output(&mut cmd).context("Failed to inspect image")?;
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        Ok(output_str.lines().any(|line| {
-            line.ends_with(image_name) || line.ends_with(&format!("localhost/{}", image_name))
-        }))
+        if !output.status.success() {
+            return Err(
+                ContainerError::CommandFailed(format!("image inspect {}", image_name)).into(),
+            );
+        }
+
+        // Podman prints the bare hex ID, Docker prefixes it with the algorithm
+        let id = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        if id.starts_with("sha256:") {
+            Ok(id)
+        } else {
+            Ok(format!("sha256:{}", id))
+        }
     }
 
     /// Checks if a container exists (running or stopped)
@@ -133,16 +294,19 @@ impl ContainerEngine {
     /// Returns `Ok(true)` if the container exists, `Ok(false)` if it doesn't,
     /// or an error if the check fails.
     pub fn container_exists(&self, container_name: &str) -> Result<bool> {
-        let output = Command::new(self.engine_type.as_command())
-            .arg("ps")
-            .arg("-a")
-            .arg("--format")
-            .arg("table {{.Names}}")
-            .output()
+        // Apptainer has no persistent named containers; every run starts fresh
+        if self.engine_type == EngineType::Apptainer {
+            return Ok(false);
+        }
+
+        let output = self
+            .output(&mut self.list_containers_command(true))
             .context("Failed to list containers")?;
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        Ok(output_str.lines().any(|line| line == container_name))
+        Ok(container_listed(
+            &String::from_utf8_lossy(&output.stdout),
+            container_name,
+        ))
     }
 
     /// Checks if a container is currently running
@@ -156,15 +320,78 @@ impl ContainerEngine {
     /// Returns `Ok(true)` if the container is running, `Ok(false)` if it's not,
     /// or an error if the check fails.
     pub fn container_running(&self, container_name: &str) -> Result<bool> {
-        let output = Command::new(self.engine_type.as_command())
-            .arg("ps")
-            .arg("--format")
-            .arg("table {{.Names}}")
-            .output()
+        if self.engine_type == EngineType::Apptainer {
+            return Ok(false);
+        }
+
+        let output = self
+            .output(&mut self.list_containers_command(false))
             .context("Failed to list running containers")?;
 
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        Ok(output_str.lines().any(|line| line == container_name))
+        Ok(container_listed(
+            &String::from_utf8_lossy(&output.stdout),
+            container_name,
+        ))
+    }
+
+    /// Assembles the command that lists container names, one per line
+    ///
+    /// The format has no `table` prefix, so the output has no `NAMES` header.
+    ///
+    /// # Arguments
+    ///
+    /// * `all` - Whether to include stopped containers
+    fn list_containers_command(&self, all: bool) -> Command {
+        let mut cmd = Command::new(self.engine_type.as_command());
+        cmd.arg("ps");
+        if all {
+            cmd.arg("-a");
+        }
+        cmd.arg("--format").arg("{{.Names}}");
+        cmd
+    }
+
+    /// Returns the name of the image a container was created from
+    ///
+    /// Podman's `localhost/` qualifier is stripped so the result compares
+    /// equal to the image names this tool generates.
+    ///
+    /// # Arguments
+    ///
+    /// * `container_name` - The name of an existing container
+    ///
+    /// # Returns
+    ///
+    /// Returns the image name, or an error if the container cannot be inspected.
+    pub fn container_image(&self, container_name: &str) -> Result<String> {
+        if self.engine_type == EngineType::Apptainer {
+            return Err(self.unsupported("inspecting named containers"));
+        }
+
+        let mut cmd = Command::new(self.engine_type.as_command());
+        cmd.arg("container")
+            .arg("inspect")
+            .arg("--format")
+            .arg("{{.Config.Image}}")
+            .arg(container_name);
+
+        let output = self
+            .output(&mut cmd)
+            .context("Failed to inspect container")?;
+
+        if !output.status.success() {
+            return Err(ContainerError::CommandFailed(format!(
+                "container inspect {}",
+                container_name
+            ))
+            .into());
+        }
+
+        let image = String::from_utf8_lossy(&output.stdout).trim().to_string();
+        Ok(image
+            .strip_prefix("localhost/")
+            .map(str::to_string)
+            .unwrap_or(image))
     }
 
     /// Removes a container forcefully
@@ -177,11 +404,15 @@ impl ContainerEngine {
     ///
     /// Returns `Ok(())` on success or an error if the removal fails.
     pub fn remove_container(&self, container_name: &str) -> Result<()> {
-        let status = Command::new(self.engine_type.as_command())
-            .arg("rm")
-            .arg("-f")
-            .arg(container_name)
-            .status()
+        if self.engine_type == EngineType::Apptainer {
+            return Err(self.unsupported("removing named containers"));
+        }
+
+        let mut cmd = Command::new(self.engine_type.as_command());
+        cmd.arg("rm").arg("-f").arg(container_name);
+
+        let status = self
+            .status(&mut cmd)
             .context("Failed to remove container")?;
 
         if !status.success() {
@@ -196,13 +427,110 @@ impl ContainerEngine {
     ///
     /// * `image_name` - The name to tag the built image with
     /// * `dockerfile` - Path to the Dockerfile to build from
+    /// * `progress` - Progress output style (Docker/nerdctl only, enables BuildKit)
+    /// * `build_uid` - The user ID passed as the `UID` build argument
+    /// * `build_gid` - The group ID passed as the `GID` build argument
+    /// * `retries` - How many times to retry a build that failed with a network error
     ///
     /// # Returns
     ///
     /// Returns `Ok(())` on success or an error if the build fails.
-    pub fn build_image(&self, image_name: &str, dockerfile: &Path) -> Result<()> {
-        let status = Command::new(self.engine_type.as_command())
-            .arg("build")
+    ///
+    /// When `retries` is non-zero the build output is captured (and still
+    /// echoed) so that it can be scanned for network errors, and progress
+    /// output is forced to plain. Builds that fail for any other reason are
+    /// not retried.
+    pub fn build_image(
+        &self,
+        image_name: &str,
+        dockerfile: &Path,
+        progress: BuildProgress,
+        build_uid: u32,
+        build_gid: u32,
+        retries: u32,
+    ) -> Result<()> {
+        if !self.can_build() {
+            return Err(self.unsupported(format!(
+                "building from a Dockerfile; build the image with docker or podman and \
+                 convert it with 'apptainer build {} docker-daemon://<image>'",
+                image_name
+            )));
+        }
+
+        let mut cmd = self.build_command(
+            image_name, dockerfile, progress, build_uid, build_gid, retries,
+        )?;
+
+        if retries == 0 || self.dry_run {
+            let status = self.status(&mut cmd).context("Failed to build image")?;
+            if !status.success() {
+                return Err(ContainerError::BuildFailed(image_name.to_string()).into());
+            }
+            return Ok(());
+        }
+
+        let attempts = retries + 1;
+        for attempt in 1..=attempts {
+            if attempt > 1 {
+                self.verbosity
+                    .info(format!("Retrying build (attempt {}/{})", attempt, attempts));
+            }
+
+            self.verbosity.trace(&cmd);
+            let (status, output) = run_captured(&mut cmd).context("Failed to build image")?;
+            if status.success() {
+                return Ok(());
+            }
+
+            if !is_network_error(&output) {
+                break;
+            }
+            self.verbosity.info("Build failed with a network error");
+        }
+
+        Err(ContainerError::BuildFailed(image_name.to_string()).into())
+    }
+
+    /// Assembles the command that builds an image from a Dockerfile
+    ///
+    /// Retried builds capture their output through pipes, where BuildKit
+    /// cannot draw TTY progress, so they always use plain progress output.
+    ///
+    /// # Arguments
+    ///
+    /// See [`ContainerEngine::build_image`].
+    ///
+    /// # Returns
+    ///
+    /// Returns the build command, or an error if the Dockerfile has no parent directory.
+    fn build_command(
+        &self,
+        image_name: &str,
+        dockerfile: &Path,
+        progress: BuildProgress,
+        build_uid: u32,
+        build_gid: u32,
+        retries: u32,
+    ) -> Result<Command> {
+        let mut cmd = Command::new(self.engine_type.as_command());
+        cmd.arg("build");
+
+        // --progress is a BuildKit option; podman always prints plain output
+        if matches!(self.engine_type, EngineType::Docker | EngineType::Nerdctl) {
+            let progress = if retries > 0 {
+                BuildProgress::Plain
+            } else {
+                progress
+            };
+            cmd.env("DOCKER_BUILDKIT", "1")
+                .arg("--progress")
+                .arg(progress.as_str());
+        }
+
+        cmd.arg("--build-arg")
+            .arg(format!("UID={}", build_uid))
+            .arg("--build-arg")
+            .arg(format!("GID={}", build_gid))
             .arg("-t")
             .arg(image_name)
             .arg("-f")
@@ -211,14 +539,9 @@ impl ContainerEngine {
                 dockerfile
                     .parent()
                     .context("Failed to get Dockerfile directory")?,
-            )
-            .status()
-            .context("Failed to build image")?;
+            );
 
-        if !status.success() {
-            return Err(ContainerError::BuildFailed(image_name.to_string()).into());
-        }
-        Ok(())
+        Ok(cmd)
     }
 
     /// Starts a stopped container
@@ -231,10 +554,12 @@ impl ContainerEngine {
     ///
     /// Returns `Ok(())` on success or an error if starting fails.
     pub fn start_container(&self, container_name: &str) -> Result<()> {
-        let status = Command::new(self.engine_type.as_command())
-            .arg("start")
-            .arg(container_name)
-            .status()
+        if self.engine_type == EngineType::Apptainer {
+            return Err(self.unsupported("starting named containers"));
+        }
+
+        let status = self
+            .status(&mut self.start_command(container_name))
             .context("Failed to start container")?;
 
         if !status.success() {
@@ -243,6 +568,17 @@ impl ContainerEngine {
         Ok(())
     }
 
+    /// Assembles the command that starts a stopped container
+    ///
+    /// # Arguments
+    ///
+    /// * `container_name` - The name of the container to start
+    pub fn start_command(&self, container_name: &str) -> Command {
+        let mut cmd = Command::new(self.engine_type.as_command());
+        cmd.arg("start").arg(container_name);
+        cmd
+    }
+
     /// Executes a command in a running container
     ///
     /// This method executes either a custom command or a default bash shell
@@ -267,28 +603,21 @@ impl ContainerEngine {
         user_uid: u32,
         user_gid: u32,
     ) -> Result<()> {
-        let mut cmd = Command::new(self.engine_type.as_command());
-        cmd.arg("exec")
-            .arg("-it")
-            .arg("--user")
-            .arg(format!("{}:{}", user_uid, user_gid))
-            .arg("-e")
-            .arg(format!("UID={}", user_uid))
-            .arg("-e")
-            .arg(format!("GID={}", user_gid))
-            .arg("-w")
-            .arg(current_dir)
-            .arg(container_name);
-
-        if custom_command.is_empty() {
-            cmd.arg("/bin/bash");
-        } else {
-            for arg in custom_command {
-                cmd.arg(arg);
-            }
+        if self.engine_type == EngineType::Apptainer {
+            return Err(self.unsupported("executing in a running named container"));
         }
 
-        let status = cmd.status().context("Failed to exec into container")?;
+        let mut cmd = self.exec_command(
+            container_name,
+            custom_command,
+            current_dir,
+            user_uid,
+            user_gid,
+        );
+
+        let status = self
+            .status(&mut cmd)
+            .context("Failed to exec into container")?;
 
         if !status.success() {
             let command_str = if custom_command.is_empty() {
@@ -305,6 +634,34 @@ impl ContainerEngine {
         Ok(())
     }
 
+    /// Assembles the command that executes in a running container
+    ///
+    /// See [`ContainerEngine::exec_container`] for the meaning of the arguments.
+    pub fn exec_command(
+        &self,
+        container_name: &str,
+        custom_command: &[String],
+        current_dir: &Path,
+        user_uid: u32,
+        user_gid: u32,
+    ) -> Command {
+        let mut cmd = Command::new(self.engine_type.as_command());
+        cmd.arg("exec")
+            .arg("-it")
+            .arg("--user")
+            .arg(format!("{}:{}", user_uid, user_gid))
+            .arg("-e")
+            .arg(format!("UID={}", user_uid))
+            .arg("-e")
+            .arg(format!("GID={}", user_gid))
+            .arg("-w")
+            .arg(current_dir)
+            .arg(container_name);
+
+        Self::push_command(&mut cmd, custom_command);
+        cmd
+    }
+
     /// Creates and runs a new container with the specified configuration
     ///
     /// This method creates a new container with:
@@ -328,6 +685,7 @@ impl ContainerEngine {
     /// # Returns
     ///
     /// Returns `Ok(())` when the container session ends, or an error if creation/running fails.
+    #[allow(clippy::too_many_arguments)]
     pub fn create_and_run_container(
         &self,
         container_name: &str,
@@ -338,12 +696,66 @@ impl ContainerEngine {
         user_uid: u32,
         user_gid: u32,
     ) -> Result<()> {
+        let mut cmd = self.run_command(
+            Some(container_name),
+            image_name,
+            mount_dir,
+            custom_command,
+            current_dir,
+            user_uid,
+            user_gid,
+        );
+
+        let status = self
+            .status(&mut cmd)
+            .context("Failed to create and run container")?;
+
+        if !status.success() {
+            return Err(
+                ContainerError::CommandFailed(format!("run container {}", container_name)).into(),
+            );
+        }
+        Ok(())
+    }
+
+    /// Assembles the command that creates and runs a new container
+    ///
+    /// See [`ContainerEngine::create_and_run_container`] for the meaning of the
+    /// arguments. Without a `container_name` the command runs a disposable,
+    /// unnamed container with `--rm`, which cannot collide with existing ones.
+    #[allow(clippy::too_many_arguments)]
+    pub fn run_command(
+        &self,
+        container_name: Option<&str>,
+        image_name: &str,
+        mount_dir: &Path,
+        custom_command: &[String],
+        current_dir: &Path,
+        user_uid: u32,
+        user_gid: u32,
+    ) -> Command {
         let mut cmd = Command::new(self.engine_type.as_command());
-        cmd.arg("run")
-            .arg("-it")
-            .arg("--name")
-            .arg(container_name)
-            .arg("--user")
+        if self.engine_type == EngineType::Apptainer {
+            // Apptainer runs as the invoking user, so no --user/UID/GID mapping
+            cmd.arg("exec")
+                .arg("--bind")
+                .arg(format!("{}:{}", mount_dir.display(), mount_dir.display()))
+                .arg("--pwd")
+                .arg(current_dir);
+            for arg in &self.nvidia_args {
+                cmd.arg(arg);
+            }
+            cmd.arg(image_name);
+            Self::push_command(&mut cmd, custom_command);
+            return cmd;
+        }
+
+        cmd.arg("run").arg("-it");
+        match container_name {
+            Some(container_name) => cmd.arg("--name").arg(container_name),
+            None => cmd.arg("--rm"),
+        };
+        cmd.arg("--user")
             .arg(format!("{}:{}", user_uid, user_gid))
             .arg("-e")
             .arg(format!("UID={}", user_uid))
@@ -361,6 +773,12 @@ impl ContainerEngine {
 
         cmd.arg(image_name);
 
+        Self::push_command(&mut cmd, custom_command);
+        cmd
+    }
+
+    /// Appends the custom command, or `/bin/bash` if none was given
+    fn push_command(cmd: &mut Command, custom_command: &[String]) {
         if custom_command.is_empty() {
             cmd.arg("/bin/bash");
         } else {
@@ -368,14 +786,254 @@ impl ContainerEngine {
                 cmd.arg(arg);
             }
         }
+    }
+}
 
-        let status = cmd.status().context("Failed to create and run container")?;
+/// Returns whether `images` output lists exactly the given `repository:tag`
+///
+/// Podman qualifies local images as `localhost/<name>`, so that form matches
+/// too. Other names that merely end with `image_name` do not.
+fn image_listed(output: &str, image_name: &str) -> bool {
+    output
+        .lines()
+        .map(str::trim)
+        .any(|line| line == image_name || line.strip_prefix("localhost/") == Some(image_name))
+}
 
-        if !status.success() {
-            return Err(
-                ContainerError::CommandFailed(format!("run container {}", container_name)).into(),
-            );
+/// Returns whether `ps --format {{.Names}}` output lists the given container
+fn container_listed(output: &str, container_name: &str) -> bool {
+    output.lines().any(|line| line.trim() == container_name)
+}
+
+/// Output fragments that indicate a transient network failure during a build
+const NETWORK_ERROR_PATTERNS: &[&str] = &[
+    "Temporary failure resolving",
+    "Temporary failure in name resolution",
+    "Could not resolve",
+    "Could not connect to",
+    "Connection timed out",
+    "Connection refused",
+    "Connection reset by peer",
+    "Network is unreachable",
+    "TLS handshake timeout",
+    "i/o timeout",
+    "ReadTimeoutError",
+    "Max retries exceeded",
+    "Failed to fetch",
+];
+
+/// Returns whether build output contains a known network error
+fn is_network_error(output: &str) -> bool {
+    NETWORK_ERROR_PATTERNS
+        .iter()
+        .any(|pattern| output.contains(pattern))
+}
+
+/// Runs a command, echoing its output while also capturing it
+///
+/// # Returns
+///
+/// Returns the exit status and the combined stdout and stderr output.
+fn run_captured(cmd: &mut Command) -> io::Result<(ExitStatus, String)> {
+    let mut child = cmd.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+
+    let stdout = child.stdout.take().expect("stdout is piped");
+    let stderr = child.stderr.take().expect("stderr is piped");
+    let stdout_thread = thread::spawn(move || tee(stdout, io::stdout()));
+    let stderr_thread = thread::spawn(move || tee(stderr, io::stderr()));
+
+    let status = child.wait()?;
+    let mut output = stdout_thread.join().unwrap_or_default();
+    output.push_str(&stderr_thread.join().unwrap_or_default());
+
+    Ok((status, output))
+}
+
+/// Copies a reader to a writer line by line and returns everything copied
+///
+/// Output is forwarded as raw bytes and drained to EOF, so invalid UTF-8 (e.g.
+/// Latin-1 in package manager output) neither stops the copy nor closes the
+/// pipe on the child. The captured text is converted lossily.
+fn tee(reader: impl Read, mut writer: impl Write) -> String {
+    let mut reader = BufReader::new(reader);
+    let mut captured = Vec::new();
+    let mut line = Vec::new();
+    loop {
+        line.clear();
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) => {
+                let _ = writer.write_all(&line);
+                let _ = writer.flush();
+                captured.extend_from_slice(&line);
+            }
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(_) => break,
         }
-        Ok(())
+    }
+    String::from_utf8_lossy(&captured).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_progress_from_str() {
+        assert_eq!(
+            "plain".parse::<BuildProgress>().unwrap(),
+            BuildProgress::Plain
+        );
+        assert_eq!("TTY".parse::<BuildProgress>().unwrap(), BuildProgress::Tty);
+        assert_eq!(
+            "auto".parse::<BuildProgress>().unwrap(),
+            BuildProgress::Auto
+        );
+        assert!("fancy".parse::<BuildProgress>().is_err());
+    }
+
+    #[test]
+    fn test_image_listed() {
+        let docker = "x-dev-env:latest\ndev-env:old\nubuntu:22.04\n";
+        assert!(!image_listed(docker, "dev-env:latest"));
+        assert!(image_listed(docker, "ubuntu:22.04"));
+
+        let podman = "localhost/a1b2c3d4e5f6:latest\ndocker.io/library/ubuntu:22.04\n";
+        assert!(image_listed(podman, "a1b2c3d4e5f6:latest"));
+        assert!(!image_listed(podman, "b1b2c3d4e5f6:latest"));
+        assert!(!image_listed(podman, "ubuntu:22.04"));
+    }
+
+    #[test]
+    fn test_container_listed() {
+        let output = "a1b2c3d4e5f6\nmyproj-main\n";
+        assert!(container_listed(output, "myproj-main"));
+        assert!(!container_listed(output, "myproj"));
+        assert!(!container_listed("", "myproj-main"));
+    }
+
+    #[test]
+    fn test_list_containers_command_has_no_header() {
+        let engine = ContainerEngine::stub(EngineType::Podman, false);
+        assert_eq!(
+            shell::command_line(&engine.list_containers_command(true)),
+            "podman ps -a --format '{{.Names}}'"
+        );
+        assert_eq!(
+            shell::command_line(&engine.list_containers_command(false)),
+            "podman ps --format '{{.Names}}'"
+        );
+    }
+
+    #[test]
+    fn test_is_network_error() {
+        assert!(is_network_error(
+            "W: Failed to fetch http://archive.ubuntu.com/ubuntu/dists/noble/InRelease  \
+             Temporary failure resolving 'archive.ubuntu.com'"
+        ));
+        assert!(is_network_error(
+            "pip._vendor.urllib3.exceptions.ReadTimeoutError: HTTPSConnectionPool"
+        ));
+        assert!(!is_network_error(
+            "E: Unable to locate package does-not-exist"
+        ));
+    }
+
+    #[test]
+    fn test_tee_survives_invalid_utf8() {
+        let input: &[u8] = b"Get:1 http://archive.ubuntu.com caf\xe9\n\
+            E: Temporary failure resolving 'archive.ubuntu.com'\n";
+        let mut echoed = Vec::new();
+
+        let captured = tee(input, &mut echoed);
+
+        assert_eq!(echoed, input);
+        assert!(is_network_error(&captured));
+    }
+
+    #[test]
+    fn test_run_captured_drains_invalid_utf8() {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c")
+            .arg("printf 'caf\\377\\n'; echo 'Temporary failure resolving'; exit 1");
+
+        let (status, output) = run_captured(&mut cmd).unwrap();
+
+        assert_eq!(status.code(), Some(1));
+        assert!(is_network_error(&output));
+    }
+
+    #[test]
+    fn test_apptainer_run_command() {
+        let engine = ContainerEngine {
+            engine_type: EngineType::Apptainer,
+            nvidia_args: vec!["--nv".to_string()],
+            dry_run: false,
+            verbosity: Verbosity::Normal,
+        };
+
+        let cmd = engine.run_command(
+            Some("a1b2c3d4e5f6"),
+            "/project/a1b2c3d4e5f6.sif",
+            Path::new("/project"),
+            &["make".to_string(), "test".to_string()],
+            Path::new("/project/src"),
+            1000,
+            1000,
+        );
+
+        assert_eq!(
+            shell::command_line(&cmd),
+            "apptainer exec --bind /project:/project --pwd /project/src --nv \
+             /project/a1b2c3d4e5f6.sif make test"
+        );
+    }
+
+    #[test]
+    fn test_retried_build_uses_plain_progress() {
+        let engine = ContainerEngine {
+            engine_type: EngineType::Docker,
+            nvidia_args: Vec::new(),
+            dry_run: false,
+            verbosity: Verbosity::Normal,
+        };
+        let build = |retries| {
+            let cmd = engine
+                .build_command(
+                    "a1b2c3d4e5f6:u1000-g1000",
+                    Path::new("/project/Dockerfile"),
+                    BuildProgress::Tty,
+                    1000,
+                    1000,
+                    retries,
+                )
+                .unwrap();
+            shell::command_line(&cmd)
+        };
+
+        assert!(build(0).contains("--progress tty"));
+        assert!(build(2).contains("--progress plain"));
+    }
+
+    #[test]
+    fn test_apptainer_build_unsupported() {
+        let engine = ContainerEngine {
+            engine_type: EngineType::Apptainer,
+            nvidia_args: Vec::new(),
+            dry_run: false,
+            verbosity: Verbosity::Normal,
+        };
+
+        let err = engine
+            .build_image(
+                "/project/a1b2c3d4e5f6.sif",
+                Path::new("/project/Dockerfile"),
+                BuildProgress::Plain,
+                1000,
+                1000,
+                0,
+            )
+            .unwrap_err();
+        assert!(err.to_string().starts_with("Not supported on apptainer"));
     }
 }
@@ -4,7 +4,9 @@
 //! by searching upward from the current directory through the filesystem
 //! hierarchy until reaching the home directory or filesystem root.
 
+use anyhow::{Context, Result};
 use std::env;
+use std::fs;
 use std::path::{Path, PathBuf};
 
 /// Utility for locating Dockerfiles in the filesystem
@@ -72,4 +74,43 @@ impl DockerfileLocator {
 
         None
     }
+
+    /// Resolves symlinks in a Dockerfile path to its real location
+    ///
+    /// When a Dockerfile is a symlink (e.g. into a dotfiles repository), the
+    /// mount directory, build context and lockfile are all derived from its
+    /// parent directory. Canonicalizing the path makes them resolve against
+    /// the directory of the real file rather than the directory of the link.
+    ///
+    /// # Arguments
+    ///
+    /// * `dockerfile` - Path to the Dockerfile, possibly a symlink
+    ///
+    /// # Returns
+    ///
+    /// Returns the canonical, absolute path to the Dockerfile, or an error if
+    /// the path cannot be resolved.
+    pub fn resolve_symlinks(dockerfile: &Path) -> Result<PathBuf> {
+        fs::canonicalize(dockerfile).with_context(|| {
+            format!(
+                "Failed to resolve Dockerfile path: {}",
+                dockerfile.display()
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testutil::SymlinkedDockerfile;
+
+    #[test]
+    fn test_resolve_symlinks() {
+        let fixture = SymlinkedDockerfile::new("symlink");
+
+        let resolved = DockerfileLocator::resolve_symlinks(&fixture.link).unwrap();
+        assert_eq!(resolved, fixture.real_dir.join("Dockerfile"));
+        assert_eq!(resolved.parent().unwrap(), fixture.real_dir.as_path());
+    }
 }
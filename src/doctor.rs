@@ -0,0 +1,399 @@
+//! Environment diagnostics
+//!
+//! This module implements the `--doctor` checks, which inspect the host for
+//! the most common setup problems (missing engine, unreachable daemon, GPU
+//! detection, group membership, rootless configuration, lockfile state) and
+//! print a checklist with remediation hints.
+
+use anyhow::Result;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+use crate::engine::EngineType;
+use crate::lockfile::Lockfile;
+
+/// Outcome of a single diagnostic check
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CheckStatus {
+    /// Informational only; nothing was judged
+    Info,
+    /// The check succeeded
+    Pass,
+    /// Something is degraded but containers can still be used
+    Warn,
+    /// Containers cannot be used until this is fixed
+    Fail,
+}
+
+impl CheckStatus {
+    /// Returns the checklist marker for this status
+    fn label(&self) -> &'static str {
+        match self {
+            CheckStatus::Info => "INFO",
+            CheckStatus::Pass => "PASS",
+            CheckStatus::Warn => "WARN",
+            CheckStatus::Fail => "FAIL",
+        }
+    }
+}
+
+/// Result of a single diagnostic check
+#[derive(Debug)]
+pub struct Check {
+    /// Status of the check
+    pub status: CheckStatus,
+    /// Short description of what was found
+    pub message: String,
+    /// Suggested remediation when the check did not pass
+    pub hint: Option<String>,
+}
+
+impl Check {
+    /// Creates an informational entry that is neither a pass nor a failure
+    fn info(message: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Info,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    /// Creates a passed check
+    fn pass(message: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Pass,
+            message: message.into(),
+            hint: None,
+        }
+    }
+
+    /// Creates a warning with a remediation hint
+    fn warn(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Warn,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+
+    /// Creates a failed check with a remediation hint
+    fn fail(message: impl Into<String>, hint: impl Into<String>) -> Self {
+        Self {
+            status: CheckStatus::Fail,
+            message: message.into(),
+            hint: Some(hint.into()),
+        }
+    }
+}
+
+/// Runs all diagnostic checks and prints a checklist
+///
+/// # Arguments
+///
+/// * `engine_type` - The container engine that would be used, or the error from selecting one
+/// * `dockerfile` - The Dockerfile that would be used, if one was found
+///
+/// # Returns
+///
+/// Returns `Ok(())` if no check failed, or an error summarizing the number
+/// of failed checks.
+pub fn run(engine_type: Result<EngineType>, dockerfile: Option<&Path>) -> Result<()> {
+    let mut checks = Vec::new();
+
+    match engine_type {
+        Ok(engine_type) => checks.extend(check_engine(engine_type)),
+        Err(err) => checks.push(Check::fail(
+            format!("{:#}", err),
+            "Install docker or podman, or set CONTAINER_ENGINE",
+        )),
+    }
+    checks.push(check_gpu());
+    checks.extend(check_dockerfile(dockerfile));
+
+    for check in &checks {
+        println!("[{}] {}", check.status.label(), check.message);
+        if let Some(hint) = &check.hint {
+            println!("       {}", hint);
+        }
+    }
+
+    let failed = checks
+        .iter()
+        .filter(|check| check.status == CheckStatus::Fail)
+        .count();
+    if failed > 0 {
+        anyhow::bail!("{} check(s) failed", failed);
+    }
+    Ok(())
+}
+
+/// Runs the checks specific to the selected engine
+fn check_engine(engine_type: EngineType) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let engine_installed = which::which(engine_type.as_command()).is_ok();
+    checks.push(check_engine_installed(engine_type, engine_installed));
+    if engine_installed {
+        checks.push(check_engine_version(engine_type));
+        // Apptainer is daemonless and has no 'info' command
+        if engine_type != EngineType::Apptainer {
+            checks.push(check_engine_reachable(engine_type));
+        }
+    }
+    match engine_type {
+        EngineType::Docker => checks.push(check_docker_group()),
+        EngineType::Podman => checks.push(check_rootless_podman()),
+        EngineType::Nerdctl | EngineType::Apptainer => {}
+    }
+
+    checks
+}
+
+/// Checks that the engine executable is on the PATH
+fn check_engine_installed(engine_type: EngineType, installed: bool) -> Check {
+    if installed {
+        Check::pass(format!("{} is installed", engine_type))
+    } else {
+        Check::fail(
+            format!("{} not found in PATH", engine_type),
+            "Install it, or select another engine with CONTAINER_ENGINE",
+        )
+    }
+}
+
+/// Reports the engine version
+///
+/// No minimum version is enforced, so the version is shown as information
+/// rather than as a passed check.
+fn check_engine_version(engine_type: EngineType) -> Check {
+    match Command::new(engine_type.as_command())
+        .arg("--version")
+        .output()
+    {
+        Ok(output) if output.status.success() => {
+            Check::info(String::from_utf8_lossy(&output.stdout).trim().to_string())
+        }
+        _ => Check::warn(
+            format!("Could not determine {} version", engine_type),
+            format!("Run '{} --version' to investigate", engine_type),
+        ),
+    }
+}
+
+/// Checks that the engine can talk to its daemon or storage backend
+fn check_engine_reachable(engine_type: EngineType) -> Check {
+    let reachable = Command::new(engine_type.as_command())
+        .arg("info")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+
+    if reachable {
+        Check::pass(format!("{} is reachable", engine_type))
+    } else {
+        let hint = match engine_type {
+            EngineType::Docker => {
+                "Start the Docker daemon and check permissions on /var/run/docker.sock"
+            }
+            EngineType::Podman => "Run 'podman info' to see the underlying error",
+            EngineType::Nerdctl => "Check that containerd is running and its socket is accessible",
+            EngineType::Apptainer => "Run 'apptainer --version' to check the installation",
+        };
+        Check::fail(format!("'{} info' failed", engine_type), hint)
+    }
+}
+
+/// Checks whether NVIDIA GPU support will be enabled
+fn check_gpu() -> Check {
+    if which::which("nvidia-smi").is_err() {
+        return Check::warn(
+            "nvidia-smi not found, GPU support disabled",
+            "Install the NVIDIA driver if this machine has a GPU",
+        );
+    }
+
+    let working = Command::new("nvidia-smi")
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(|status| status.success())
+        .unwrap_or(false);
+
+    if working {
+        Check::pass("NVIDIA GPU detected")
+    } else {
+        Check::warn(
+            "nvidia-smi failed, GPU support disabled",
+            "Run 'nvidia-smi' to check the driver installation",
+        )
+    }
+}
+
+/// Checks that a non-root user may talk to the Docker daemon
+fn check_docker_group() -> Check {
+    if users::get_current_uid() == 0 {
+        return Check::pass("Running as root");
+    }
+
+    let in_group = users::group_access_list()
+        .map(|groups| groups.iter().any(|group| group.name() == "docker"))
+        .unwrap_or(false);
+
+    if in_group {
+        Check::pass("User is in the docker group")
+    } else {
+        Check::warn(
+            "User is not in the docker group",
+            "Run 'sudo usermod -aG docker $USER' and log in again, unless Docker is rootless",
+        )
+    }
+}
+
+/// Checks that subordinate IDs are configured for rootless podman
+fn check_rootless_podman() -> Check {
+    if users::get_current_uid() == 0 {
+        return Check::pass("Running as root, rootless setup not required");
+    }
+
+    let Some(username) = users::get_current_username() else {
+        return Check::warn(
+            "Could not determine current user name",
+            "Check that the current UID has a passwd entry",
+        );
+    };
+    let username = username.to_string_lossy();
+
+    let has_entry = |path: &str| {
+        fs::read_to_string(path)
+            .map(|content| has_subid_entry(&content, &username))
+            .unwrap_or(false)
+    };
+
+    if has_entry("/etc/subuid") && has_entry("/etc/subgid") {
+        Check::pass("Rootless podman is configured")
+    } else {
+        Check::warn(
+            format!("No /etc/subuid or /etc/subgid entry for {}", username),
+            format!(
+                "Run 'sudo usermod --add-subuids 100000-165535 --add-subgids 100000-165535 {}'",
+                username
+            ),
+        )
+    }
+}
+
+/// Returns whether a subuid/subgid file has a range for the given user
+fn has_subid_entry(content: &str, username: &str) -> bool {
+    content
+        .lines()
+        .any(|line| line.split(':').next() == Some(username))
+}
+
+/// Checks that the Dockerfile exists and its lockfile is consistent
+fn check_dockerfile(dockerfile: Option<&Path>) -> Vec<Check> {
+    let Some(dockerfile) = dockerfile else {
+        return vec![Check::fail(
+            "No Dockerfile found",
+            "Create a Dockerfile, or pass -f/--dockerfile or set DOCKERFILE",
+        )];
+    };
+
+    if !dockerfile.exists() {
+        return vec![Check::fail(
+            format!("Dockerfile {} does not exist", dockerfile.display()),
+            "Check the -f/--dockerfile argument or the DOCKERFILE variable",
+        )];
+    }
+
+    let mut checks = vec![Check::pass(format!(
+        "Using Dockerfile {}",
+        dockerfile.display()
+    ))];
+
+    let lockfile = match Lockfile::load_or_create(dockerfile) {
+        Ok(lockfile) => lockfile,
+        Err(err) => {
+            checks.push(Check::fail(
+                format!("{:#}", err),
+                "Delete .containers.lock next to the Dockerfile to regenerate it",
+            ));
+            return checks;
+        }
+    };
+
+    if !lockfile.dockerfiles.contains_key(dockerfile) {
+        checks.push(Check::warn(
+            "Lockfile has no entry for this Dockerfile",
+            "The image will be built on the next run",
+        ));
+    } else {
+        match lockfile.has_dockerfile_changed(dockerfile) {
+            Ok(false) => checks.push(Check::pass("Lockfile is up to date")),
+            Ok(true) => checks.push(Check::warn(
+                "Dockerfile changed since the last build",
+                "The image will be rebuilt on the next run",
+            )),
+            Err(err) => checks.push(Check::fail(
+                format!("{:#}", err),
+                "Check that the Dockerfile is readable",
+            )),
+        }
+    }
+
+    checks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_subid_entry() {
+        let content = "alice:100000:65536\nbob:165536:65536\n";
+        assert!(has_subid_entry(content, "alice"));
+        assert!(has_subid_entry(content, "bob"));
+        assert!(!has_subid_entry(content, "ali"));
+        assert!(!has_subid_entry("", "alice"));
+    }
+
+    #[test]
+    fn test_symlinked_dockerfile_lockfile_lookup() {
+        use crate::config::Config;
+        use crate::testutil::SymlinkedDockerfile;
+
+        let fixture = SymlinkedDockerfile::new("doctor");
+
+        // A build with --follow-symlinks records the canonical path
+        let canonical = fixture.real_dir.join("Dockerfile");
+        let mut lockfile = Lockfile::new();
+        lockfile.update_dockerfile_info(&canonical, None).unwrap();
+        lockfile.save(&canonical).unwrap();
+
+        let lockfile_check = |follow_symlinks| {
+            let dockerfile =
+                Config::doctor_dockerfile(Some(fixture.link.clone()), follow_symlinks).unwrap();
+            check_dockerfile(Some(&dockerfile))
+                .into_iter()
+                .find(|check| check.message.starts_with("Lockfile"))
+                .unwrap()
+        };
+
+        let followed = lockfile_check(true);
+        assert_eq!(followed.message, "Lockfile is up to date");
+        assert_eq!(followed.status, CheckStatus::Pass);
+
+        // Without --follow-symlinks the lockfile next to the link has no entry
+        let unfollowed = lockfile_check(false);
+        assert_eq!(unfollowed.status, CheckStatus::Warn);
+    }
+
+    #[test]
+    fn test_missing_dockerfile_fails() {
+        let checks = check_dockerfile(None);
+        assert_eq!(checks.len(), 1);
+        assert_eq!(checks[0].status, CheckStatus::Fail);
+    }
+}
@@ -3,23 +3,36 @@
 //! This module defines the supported container engines and provides
 //! conversions between string representations and the typed enum.
 
+use std::env;
+use std::ffi::OsStr;
 use std::fmt;
+use std::path::PathBuf;
 use std::str::FromStr;
 
+use crate::errors::ContainerError;
+
 /// Supported container engine types
 ///
 /// This enum represents the container engines that the application can work with.
 /// Each variant corresponds to a specific container runtime with its own
 /// command-line interface and behavior.
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
 pub enum EngineType {
     /// Docker container engine
     Docker,
     /// Podman container engine
+    #[default]
     Podman,
+    /// nerdctl, the Docker-compatible CLI for containerd
+    Nerdctl,
+    /// Apptainer (formerly Singularity), the daemonless HPC container runtime
+    Apptainer,
 }
 
 impl EngineType {
+    /// Engines probed, in order of preference, when none is configured
+    pub const AUTO_DETECT_ORDER: [EngineType; 2] = [EngineType::Docker, EngineType::Podman];
+
     /// Returns the command name for this engine type
     ///
     /// This is the executable name that should be used when invoking
@@ -32,8 +45,37 @@ impl EngineType {
         match self {
             EngineType::Docker => "docker",
             EngineType::Podman => "podman",
+            EngineType::Nerdctl => "nerdctl",
+            EngineType::Apptainer => "apptainer",
         }
     }
+
+    /// Picks the first engine from [`Self::AUTO_DETECT_ORDER`] found in `PATH`
+    ///
+    /// # Returns
+    ///
+    /// Returns the detected engine, or `ContainerError::EngineNotFound` if none
+    /// of the probed engines is installed.
+    pub fn detect() -> Result<Self, ContainerError> {
+        Self::detect_in(env::var_os("PATH"))
+    }
+
+    /// Picks the first engine from [`Self::AUTO_DETECT_ORDER`] found in `paths`
+    fn detect_in(paths: Option<impl AsRef<OsStr>>) -> Result<Self, ContainerError> {
+        let cwd = env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+
+        Self::AUTO_DETECT_ORDER
+            .into_iter()
+            .find(|engine| which::which_in(engine.as_command(), paths.as_ref(), &cwd).is_ok())
+            .ok_or_else(|| {
+                let searched = Self::AUTO_DETECT_ORDER
+                    .iter()
+                    .map(|engine| engine.as_command())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                ContainerError::EngineNotFound(searched)
+            })
+    }
 }
 
 impl fmt::Display for EngineType {
@@ -49,17 +91,13 @@ impl FromStr for EngineType {
         match s.to_lowercase().as_str() {
             "docker" => Ok(EngineType::Docker),
             "podman" => Ok(EngineType::Podman),
+            "nerdctl" | "containerd" => Ok(EngineType::Nerdctl),
+            "apptainer" | "singularity" => Ok(EngineType::Apptainer),
             _ => Err(format!("Unknown engine type: {}", s)),
         }
     }
 }
 
-impl Default for EngineType {
-    fn default() -> Self {
-        EngineType::Podman
-    }
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -69,6 +107,22 @@ mod tests {
         assert_eq!("docker".parse::<EngineType>().unwrap(), EngineType::Docker);
         assert_eq!("podman".parse::<EngineType>().unwrap(), EngineType::Podman);
         assert_eq!("DOCKER".parse::<EngineType>().unwrap(), EngineType::Docker);
+        assert_eq!(
+            "nerdctl".parse::<EngineType>().unwrap(),
+            EngineType::Nerdctl
+        );
+        assert_eq!(
+            "containerd".parse::<EngineType>().unwrap(),
+            EngineType::Nerdctl
+        );
+        assert_eq!(
+            "apptainer".parse::<EngineType>().unwrap(),
+            EngineType::Apptainer
+        );
+        assert_eq!(
+            "singularity".parse::<EngineType>().unwrap(),
+            EngineType::Apptainer
+        );
         assert!("unknown".parse::<EngineType>().is_err());
     }
 
@@ -76,6 +130,8 @@ mod tests {
     fn test_as_command() {
         assert_eq!(EngineType::Docker.as_command(), "docker");
         assert_eq!(EngineType::Podman.as_command(), "podman");
+        assert_eq!(EngineType::Nerdctl.as_command(), "nerdctl");
+        assert_eq!(EngineType::Apptainer.as_command(), "apptainer");
     }
 
     #[test]
@@ -84,6 +140,38 @@ mod tests {
         assert_eq!(format!("{}", EngineType::Podman), "podman");
     }
 
+    #[test]
+    fn test_detect_in() {
+        use crate::testutil::TempDir;
+        use std::fs;
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp = TempDir::new("detect");
+        let dir = temp.path();
+        let stub = |name: &str| {
+            let path = dir.join(name);
+            fs::write(&path, "#!/bin/sh\n").unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+        };
+
+        assert!(matches!(
+            EngineType::detect_in(Some(dir)),
+            Err(ContainerError::EngineNotFound(_))
+        ));
+
+        stub("podman");
+        assert_eq!(
+            EngineType::detect_in(Some(dir)).unwrap(),
+            EngineType::Podman
+        );
+
+        stub("docker");
+        assert_eq!(
+            EngineType::detect_in(Some(dir)).unwrap(),
+            EngineType::Docker
+        );
+    }
+
     #[test]
     fn test_default() {
         assert_eq!(EngineType::default(), EngineType::Podman);
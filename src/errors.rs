@@ -23,8 +23,30 @@ pub enum ContainerError {
 
     /// Container engine command execution failed
     ///
-    /// This error occurs when a container engine command (docker/podman)
+    /// This error occurs when a container engine command (docker/podman/nerdctl/apptainer)
     /// returns a non-zero exit status, indicating the operation failed.
     #[error("Command execution failed: {0}")]
     CommandFailed(String),
+
+    /// No container engine could be found
+    ///
+    /// This error occurs when `CONTAINER_ENGINE` is unset and none of the
+    /// automatically probed engines is installed.
+    #[error(
+        "No container engine found (looked for {0} in PATH); install one or set CONTAINER_ENGINE"
+    )]
+    EngineNotFound(String),
+
+    /// Operation is not available on the selected container engine
+    ///
+    /// This error occurs when an operation has no equivalent on the engine,
+    /// such as building from a Dockerfile or managing named containers with
+    /// Apptainer.
+    #[error("Not supported on {engine}: {operation}")]
+    Unsupported {
+        /// The engine the operation was attempted on
+        engine: String,
+        /// Description of the unsupported operation and any workaround
+        operation: String,
+    },
 }
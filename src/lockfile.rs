@@ -34,6 +34,9 @@ pub struct DockerfileInfo {
     pub modified_time: u64,
     /// Size of the Dockerfile in bytes
     pub size: u64,
+    /// Content-addressable ID of the image built from this Dockerfile
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_digest: Option<String>,
 }
 
 impl Lockfile {
@@ -105,12 +108,18 @@ impl Lockfile {
     /// # Arguments
     ///
     /// * `dockerfile_path` - Path to the Dockerfile to update information for
+    /// * `image_digest` - Digest of the image built from the Dockerfile, if known
     ///
     /// # Returns
     ///
     /// Returns `Ok(())` on success or an error if updating fails.
-    pub fn update_dockerfile_info(&mut self, dockerfile_path: &Path) -> Result<()> {
-        let info = DockerfileInfo::from_path(dockerfile_path)?;
+    pub fn update_dockerfile_info(
+        &mut self,
+        dockerfile_path: &Path,
+        image_digest: Option<String>,
+    ) -> Result<()> {
+        let mut info = DockerfileInfo::from_path(dockerfile_path)?;
+        info.image_digest = image_digest;
         self.dockerfiles.insert(dockerfile_path.to_path_buf(), info);
         Ok(())
     }
@@ -186,6 +195,7 @@ impl DockerfileInfo {
             content_hash,
             modified_time,
             size: content.len() as u64,
+            image_digest: None,
         })
     }
 
@@ -203,3 +213,45 @@ impl Default for Lockfile {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lockfile_without_image_digest() {
+        let content = r#"{
+            "version": 1,
+            "dockerfiles": {
+                "/project/Dockerfile": {
+                    "content_hash": "abc",
+                    "modified_time": 1,
+                    "size": 2
+                }
+            }
+        }"#;
+
+        let lockfile: Lockfile = serde_json::from_str(content).unwrap();
+        let info = &lockfile.dockerfiles[Path::new("/project/Dockerfile")];
+        assert_eq!(info.image_digest, None);
+    }
+
+    #[test]
+    fn test_image_digest_roundtrip() {
+        let mut lockfile = Lockfile::new();
+        lockfile.dockerfiles.insert(
+            PathBuf::from("/project/Dockerfile"),
+            DockerfileInfo {
+                content_hash: "abc".to_string(),
+                modified_time: 1,
+                size: 2,
+                image_digest: Some("sha256:0123".to_string()),
+            },
+        );
+
+        let content = serde_json::to_string(&lockfile).unwrap();
+        let parsed: Lockfile = serde_json::from_str(&content).unwrap();
+        let info = &parsed.dockerfiles[Path::new("/project/Dockerfile")];
+        assert_eq!(info.image_digest.as_deref(), Some("sha256:0123"));
+    }
+}
@@ -8,35 +8,54 @@ use anyhow::{Context, Result};
 use clap::Parser;
 use std::env;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Instant;
 
 mod config;
 mod container;
 mod dockerfile;
+mod doctor;
 mod engine;
 mod errors;
 mod lockfile;
+mod naming;
+mod shell;
+#[cfg(test)]
+mod testutil;
 
 use config::Config;
-use container::ContainerEngine;
+use container::{BuildProgress, ContainerEngine};
 
 /// Command-line arguments structure for the container management utility
 #[derive(Parser)]
 #[command(
     name = "containers",
+    version,
     about = "Create or enter a container environment",
     after_help = "ENVIRONMENT VARIABLES:
   CONTAINER_NAME          Set default container name
   DOCKERFILE              Set default Dockerfile path
-  CONTAINER_ENGINE        Container engine to use (default: podman)
+  CONTAINER_ENGINE        Container engine: docker, podman, nerdctl or apptainer
+                          (default: first of docker, podman found in PATH)
+  BUILD_UID / BUILD_GID   UID/GID build arguments (default: current user)
 
 EXAMPLES:
   containers                      Use default settings
   containers mycontainer          Use custom container name
+  containers 'myproj-{branch}'    One container per git branch
   containers -f custom.dockerfile Use custom Dockerfile
   containers -u                   Update/rebuild image and container
+  containers -u --progress plain  Rebuild with CI-friendly build output
+  containers -u --build-retries 3 Retry builds that hit network errors
+  containers --follow-symlinks    Mount the real directory of a symlinked Dockerfile
   containers -- echo hello       Run custom command in container
-  CONTAINER_ENGINE=docker containers    Use Docker instead of Podman"
+  containers -u --dry-run         Show the rebuild and run commands without running them
+  containers -v                   Echo each engine command before running it
+  containers -q -- make test      Run a command without status messages
+  containers --emit-script run.sh Save the container command as a script
+  containers --doctor             Check engine, GPU and lockfile setup
+  CONTAINER_ENGINE=podman containers    Use Podman even if Docker is installed"
 )]
 struct Args {
     /// Use specified Dockerfile (default: search current dir upward)
@@ -47,7 +66,47 @@ struct Args {
     #[arg(short, long)]
     update: bool,
 
-    /// Name for the container (default: based on Dockerfile directory)
+    /// Build progress output: plain, tty or auto (default: plain when not a terminal)
+    #[arg(long, value_name = "MODE")]
+    progress: Option<BuildProgress>,
+
+    /// Retry the build up to N times when it fails with a network error
+    #[arg(long, value_name = "N", default_value_t = 0)]
+    build_retries: u32,
+
+    /// Build the image for UID/GID 1000 instead of the current user (e.g. for CI)
+    #[arg(long)]
+    generic_image: bool,
+
+    /// Resolve a symlinked Dockerfile to its real location
+    #[arg(long)]
+    follow_symlinks: bool,
+
+    /// Print the engine commands that would change state instead of running them
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Print how long the build and the container run took
+    #[arg(long)]
+    time: bool,
+
+    /// Write a standalone run command for the image to an executable script and exit
+    #[arg(long, value_name = "PATH")]
+    emit_script: Option<PathBuf>,
+
+    /// Print more detail; -v also echoes every engine command to stderr
+    #[arg(short, long, action = clap::ArgAction::Count)]
+    verbose: u8,
+
+    /// Only print errors and the output of the container itself
+    #[arg(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Diagnose the container environment and exit
+    #[arg(long)]
+    doctor: bool,
+
+    /// Name for the container; may use {branch}, {dir}, {user} and {hash} (default: Dockerfile hash)
     #[arg(value_name = "CONTAINER_NAME")]
     container_name: Option<String>,
 
@@ -62,9 +121,24 @@ struct Args {
 /// and manages the complete container lifecycle.
 fn main() -> Result<()> {
     let args = Args::parse();
+
+    if args.doctor {
+        let dockerfile = Config::doctor_dockerfile(args.dockerfile, args.follow_symlinks);
+        return doctor::run(Config::engine_type_from_env(), dockerfile.as_deref());
+    }
+
     let mut config = Config::from_args_and_env(args)?;
 
-    let engine = ContainerEngine::new(config.engine_type)?;
+    let engine = ContainerEngine::new(config.engine_type, config.dry_run, config.verbosity)?;
+
+    if let Some(script) = &config.emit_script {
+        let current_dir = env::current_dir().context("Failed to get current directory")?;
+        shell::write_script(script, &[script_command(&config, &engine, &current_dir)?])?;
+        config
+            .verbosity
+            .info(format!("Wrote container command to {}", script.display()));
+        return Ok(());
+    }
 
     run_container(&mut config, &engine).context("Failed to run container")
 }
@@ -74,9 +148,18 @@ fn main() -> Result<()> {
 /// This function handles:
 /// - Checking lockfile for Dockerfile changes
 /// - Building container images when needed, when update is requested, or when Dockerfile changed
+/// - Recreating the container when it was created from a different image, e.g. one
+///   built for other build IDs, while keeping containers from pre-build-ID `:latest` images
 /// - Creating new containers or entering existing ones
 /// - Starting stopped containers
-/// - Updating lockfile after successful builds
+/// - Updating lockfile after successful builds, including the built image digest
+/// - Reporting build and run durations when `--time` is set
+///
+/// Status messages go through [`config::Verbosity`], so `-q` silences them;
+/// errors are still reported on stderr when `main` returns.
+///
+/// With `--dry-run`, the engine prints build, remove, start, exec and run
+/// commands instead of executing them, and the lockfile is left untouched.
 ///
 /// # Arguments
 ///
@@ -87,69 +170,434 @@ fn main() -> Result<()> {
 ///
 /// Returns `Ok(())` on success, or an error if any container operation fails.
 fn run_container(config: &mut Config, engine: &ContainerEngine) -> Result<()> {
+    // In dry-run mode a removal is only printed, so later queries would still see the container
+    let mut container_removed = false;
+
     // Build image if needed
     if config.dockerfile.exists() {
-        // Check lockfile for Dockerfile changes
-        let dockerfile_changed = config.lockfile.has_dockerfile_changed(&config.dockerfile)?;
+        // Check lockfile for Dockerfile changes. Engines that cannot build name their
+        // images by content hash only, so an existing image always matches the Dockerfile.
+        let dockerfile_changed =
+            engine.can_build() && config.lockfile.has_dockerfile_changed(&config.dockerfile)?;
 
         let should_build =
             config.update_image || !engine.image_exists(&config.image_name)? || dockerfile_changed;
 
         if should_build {
             if config.update_image {
-                println!("Updating image: {}", config.image_name);
+                config
+                    .verbosity
+                    .info(format!("Updating image: {}", config.image_name));
             } else if dockerfile_changed {
-                println!(
+                config.verbosity.info(format!(
                     "Dockerfile changed, rebuilding image: {}",
                     config.image_name
-                );
+                ));
             } else {
-                println!("Building image: {}", config.image_name);
+                config
+                    .verbosity
+                    .info(format!("Building image: {}", config.image_name));
             }
 
             // Remove existing container if we're rebuilding due to changes
             if (config.update_image || dockerfile_changed)
                 && engine.container_exists(&config.container_name)?
             {
-                println!("Removing existing container: {}", config.container_name);
+                config.verbosity.info(format!(
+                    "Removing existing container: {}",
+                    config.container_name
+                ));
                 engine.remove_container(&config.container_name)?;
+                container_removed = true;
             }
 
-            engine.build_image(&config.image_name, &config.dockerfile)?;
+            let build_start = Instant::now();
+            engine.build_image(
+                &config.image_name,
+                &config.dockerfile,
+                config.build_progress,
+                config.build_uid,
+                config.build_gid,
+                config.build_retries,
+            )?;
+            let build_elapsed = build_start.elapsed();
 
-            // Update lockfile with new Dockerfile state after successful build
-            config.lockfile.update_dockerfile_info(&config.dockerfile)?;
-            config.lockfile.save(&config.dockerfile)?;
+            // Nothing was built in dry-run mode, so there is no digest or state to record
+            if !config.dry_run {
+                let image_digest = engine.image_digest(&config.image_name)?;
+                config.verbosity.info(format!(
+                    "Built image: {} ({})",
+                    config.image_name, image_digest
+                ));
+                if config.time {
+                    println!("Build took {:.2?}", build_elapsed);
+                }
+
+                // Update lockfile with new Dockerfile state after successful build
+                config
+                    .lockfile
+                    .update_dockerfile_info(&config.dockerfile, Some(image_digest))?;
+                config.lockfile.save(&config.dockerfile)?;
+            }
+        }
+    }
+
+    // A container created from another image, e.g. one built for other build IDs, is stale
+    if !container_removed
+        && engine.can_build()
+        && engine.container_exists(&config.container_name)?
+    {
+        let container_image = engine.container_image(&config.container_name)?;
+        match ContainerImage::classify(config, &container_image) {
+            ContainerImage::Current => {}
+            ContainerImage::Legacy => config.verbosity.info(format!(
+                "Keeping container {} from the older image {}; run with -u to recreate it from {}",
+                config.container_name, container_image, config.image_name
+            )),
+            ContainerImage::Other => {
+                config.verbosity.info(format!(
+                    "Container {} was created from {}, recreating it from {}",
+                    config.container_name, container_image, config.image_name
+                ));
+                engine.remove_container(&config.container_name)?;
+                container_removed = true;
+            }
         }
     }
 
     // Handle container lifecycle
     let current_dir = env::current_dir().context("Failed to get current directory")?;
-    if engine.container_exists(&config.container_name)? {
-        if engine.container_running(&config.container_name)? {
-            println!("Entering running container: {}", config.container_name);
-            engine.exec_container(&config.container_name, &config.custom_command, &current_dir, config.user_uid, config.user_gid)?;
+    let state = ContainerState::current(engine, &config.container_name, container_removed)?;
+
+    if config.dry_run {
+        for cmd in container_commands(config, engine, state, &current_dir)? {
+            println!("{}", shell::command_line(&cmd));
+        }
+        return Ok(());
+    }
+
+    // Report the run time even when the container command fails, e.g. a failing test run
+    let run_start = Instant::now();
+    let result = enter_container(config, engine, state, &current_dir);
+    if config.time {
+        println!("Run took {:.2?}", run_start.elapsed());
+    }
+
+    result
+}
+
+/// How the image of an existing container relates to the configured image
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContainerImage {
+    /// The container was created from the configured image
+    Current,
+    /// The container predates build IDs in the image tag and is kept to preserve its state
+    Legacy,
+    /// The container was created from another image and must be recreated
+    Other,
+}
+
+impl ContainerImage {
+    /// Classifies the image an existing container was created from
+    fn classify(config: &Config, container_image: &str) -> Self {
+        if container_image == config.image_name {
+            ContainerImage::Current
+        } else if config.legacy_image_name().as_deref() == Some(container_image) {
+            ContainerImage::Legacy
+        } else {
+            ContainerImage::Other
+        }
+    }
+}
+
+/// State of the project container at the time it is entered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ContainerState {
+    /// No container with the configured name exists
+    Missing,
+    /// The container exists but is stopped
+    Stopped,
+    /// The container is running
+    Running,
+}
+
+impl ContainerState {
+    /// Determines the state of a container
+    ///
+    /// A container removed during this invocation is reported as missing
+    /// without asking the engine, since in dry-run mode the removal was only
+    /// printed and the engine still lists it.
+    fn current(engine: &ContainerEngine, container_name: &str, removed: bool) -> Result<Self> {
+        if removed || !engine.container_exists(container_name)? {
+            Ok(ContainerState::Missing)
+        } else if engine.container_running(container_name)? {
+            Ok(ContainerState::Running)
         } else {
-            println!("Starting existing container: {}", config.container_name);
+            Ok(ContainerState::Stopped)
+        }
+    }
+}
+
+/// Enters the container, creating or starting it first as needed
+///
+/// # Arguments
+///
+/// * `config` - Application configuration containing container settings
+/// * `engine` - Container engine abstraction for executing container operations
+/// * `state` - The state of the container, as queried or after a removal
+/// * `current_dir` - The working directory to use inside the container
+///
+/// # Returns
+///
+/// Returns `Ok(())` when the container session ends, or an error if any
+/// container operation or the command inside the container fails.
+fn enter_container(
+    config: &Config,
+    engine: &ContainerEngine,
+    state: ContainerState,
+    current_dir: &Path,
+) -> Result<()> {
+    match state {
+        ContainerState::Running => {
+            config.verbosity.info(format!(
+                "Entering running container: {}",
+                config.container_name
+            ));
+            engine.exec_container(
+                &config.container_name,
+                &config.custom_command,
+                current_dir,
+                config.user_uid,
+                config.user_gid,
+            )?;
+        }
+        ContainerState::Stopped => {
+            config.verbosity.info(format!(
+                "Starting existing container: {}",
+                config.container_name
+            ));
             engine.start_container(&config.container_name)?;
-            engine.exec_container(&config.container_name, &config.custom_command, &current_dir, config.user_uid, config.user_gid)?;
+            engine.exec_container(
+                &config.container_name,
+                &config.custom_command,
+                current_dir,
+                config.user_uid,
+                config.user_gid,
+            )?;
         }
-    } else {
-        println!("Creating new container: {}", config.container_name);
-        let mount_dir = config
-            .dockerfile
-            .parent()
-            .context("Failed to get Dockerfile directory")?;
-        engine.create_and_run_container(
+        ContainerState::Missing => {
+            config
+                .verbosity
+                .info(format!("Creating new container: {}", config.container_name));
+            let mount_dir = config
+                .dockerfile
+                .parent()
+                .context("Failed to get Dockerfile directory")?;
+            engine.create_and_run_container(
+                &config.container_name,
+                &config.image_name,
+                mount_dir,
+                &config.custom_command,
+                current_dir,
+                config.user_uid,
+                config.user_gid,
+            )?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Assembles the standalone `run` command written by `--emit-script`
+///
+/// The script must work without this tool and on other machines, so it runs a
+/// disposable `--rm` container from the image instead of referring to the
+/// local, named container. Assembling it neither builds nor inspects anything.
+///
+/// # Arguments
+///
+/// * `config` - Application configuration containing container settings
+/// * `engine` - Container engine abstraction used to assemble the command
+/// * `current_dir` - The working directory to use inside the container
+///
+/// # Returns
+///
+/// Returns the command, or an error if the Dockerfile has no parent directory to mount.
+fn script_command(
+    config: &Config,
+    engine: &ContainerEngine,
+    current_dir: &Path,
+) -> Result<Command> {
+    let mount_dir = config
+        .dockerfile
+        .parent()
+        .context("Failed to get Dockerfile directory")?;
+    Ok(engine.run_command(
+        None,
+        &config.image_name,
+        mount_dir,
+        &config.custom_command,
+        current_dir,
+        config.user_uid,
+        config.user_gid,
+    ))
+}
+
+/// Assembles the commands that would enter the container in the given state
+///
+/// Mirrors the lifecycle in [`enter_container`]: exec into a running container,
+/// start and exec into a stopped one, or create a missing one.
+///
+/// # Arguments
+///
+/// * `config` - Application configuration containing container settings
+/// * `engine` - Container engine abstraction used to assemble commands
+/// * `state` - The state of the container, as queried or after a removal
+/// * `current_dir` - The working directory to use inside the container
+///
+/// # Returns
+///
+/// Returns the commands in execution order, or an error if the Dockerfile has
+/// no parent directory to mount.
+fn container_commands(
+    config: &Config,
+    engine: &ContainerEngine,
+    state: ContainerState,
+    current_dir: &Path,
+) -> Result<Vec<Command>> {
+    let exec = || {
+        engine.exec_command(
             &config.container_name,
-            &config.image_name,
-            &mount_dir,
             &config.custom_command,
-            &current_dir,
+            current_dir,
             config.user_uid,
             config.user_gid,
-        )?;
+        )
+    };
+
+    match state {
+        ContainerState::Running => Ok(vec![exec()]),
+        ContainerState::Stopped => Ok(vec![engine.start_command(&config.container_name), exec()]),
+        ContainerState::Missing => {
+            let mount_dir = config
+                .dockerfile
+                .parent()
+                .context("Failed to get Dockerfile directory")?;
+            Ok(vec![engine.run_command(
+                Some(&config.container_name),
+                &config.image_name,
+                mount_dir,
+                &config.custom_command,
+                current_dir,
+                config.user_uid,
+                config.user_gid,
+            )])
+        }
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Verbosity;
+    use crate::engine::EngineType;
+    use crate::lockfile::Lockfile;
+
+    fn config() -> Config {
+        Config {
+            dockerfile: PathBuf::from("/project/Dockerfile"),
+            container_name: "a1b2c3d4e5f6".to_string(),
+            image_name: "a1b2c3d4e5f6:u1000-g1000".to_string(),
+            engine_type: EngineType::Docker,
+            update_image: true,
+            build_progress: BuildProgress::Plain,
+            build_retries: 0,
+            custom_command: Vec::new(),
+            dry_run: true,
+            verbosity: Verbosity::Quiet,
+            time: false,
+            emit_script: None,
+            lockfile: Lockfile::new(),
+            user_uid: 1000,
+            user_gid: 1000,
+            build_uid: 1000,
+            build_gid: 1000,
+        }
+    }
+
+    #[test]
+    fn test_script_command_is_standalone() {
+        let config = config();
+        let engine = ContainerEngine::stub(EngineType::Docker, false);
+
+        let cmd = script_command(&config, &engine, Path::new("/project")).unwrap();
+        assert_eq!(
+            shell::command_line(&cmd),
+            "docker run -it --rm --user 1000:1000 -e UID=1000 -e GID=1000 \
+             -v /project:/project -w /project a1b2c3d4e5f6:u1000-g1000 /bin/bash"
+        );
+    }
+
+    #[test]
+    fn test_classify_container_image() {
+        let config = config();
+        assert_eq!(
+            ContainerImage::classify(&config, "a1b2c3d4e5f6:u1000-g1000"),
+            ContainerImage::Current
+        );
+        assert_eq!(
+            ContainerImage::classify(&config, "a1b2c3d4e5f6:latest"),
+            ContainerImage::Legacy
+        );
+        // Same Dockerfile built for other IDs, e.g. a --generic-image run by another user
+        assert_eq!(
+            ContainerImage::classify(&config, "a1b2c3d4e5f6:u1001-g1001"),
+            ContainerImage::Other
+        );
+    }
+
+    #[test]
+    fn test_dry_run_after_removal_creates_container() {
+        let config = config();
+        let engine = ContainerEngine::stub(EngineType::Docker, true);
+
+        // The removal was only printed, so the engine must not be asked again
+        let state = ContainerState::current(&engine, &config.container_name, true).unwrap();
+        assert_eq!(state, ContainerState::Missing);
+
+        let printed: Vec<String> =
+            container_commands(&config, &engine, state, Path::new("/project"))
+                .unwrap()
+                .iter()
+                .map(shell::command_line)
+                .collect();
+        assert_eq!(
+            printed,
+            [
+                "docker run -it --name a1b2c3d4e5f6 --user 1000:1000 -e UID=1000 -e GID=1000 \
+              -v /project:/project -w /project a1b2c3d4e5f6:u1000-g1000 /bin/bash"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_container_commands_for_existing_container() {
+        let config = config();
+        let engine = ContainerEngine::stub(EngineType::Docker, true);
+        let printed = |state| -> Vec<String> {
+            container_commands(&config, &engine, state, Path::new("/project"))
+                .unwrap()
+                .iter()
+                .map(shell::command_line)
+                .collect()
+        };
+
+        let running = printed(ContainerState::Running);
+        assert_eq!(running.len(), 1);
+        assert!(running[0].starts_with("docker exec"));
+
+        let stopped = printed(ContainerState::Stopped);
+        assert_eq!(stopped[0], "docker start a1b2c3d4e5f6");
+        assert!(stopped[1].starts_with("docker exec"));
+    }
 }
@@ -0,0 +1,163 @@
+//! Container name templates
+//!
+//! This module expands placeholders in container names, so a single name
+//! setting such as `myproj-{branch}` can give each branch or worktree its
+//! own isolated container.
+//!
+//! Supported placeholders:
+//! - `{branch}` - current git branch
+//! - `{dir}` - basename of the current directory
+//! - `{user}` - current user name
+//! - `{hash}` - short Dockerfile content hash
+
+use anyhow::{Context, Result, bail};
+use std::env;
+use std::process::Command;
+
+/// Expands placeholders in a container name template and sanitizes the result
+///
+/// # Arguments
+///
+/// * `template` - Container name, possibly containing placeholders
+/// * `hash_prefix` - Short Dockerfile hash used for `{hash}`
+///
+/// # Returns
+///
+/// Returns a name that is valid for Docker and Podman, or an error if a
+/// placeholder is unknown or cannot be resolved.
+pub fn expand_container_name(template: &str, hash_prefix: &str) -> Result<String> {
+    let expanded = expand(template, |placeholder| match placeholder {
+        "hash" => Ok(hash_prefix.to_string()),
+        "dir" => current_dir_name(),
+        "user" => current_user(),
+        "branch" => git_branch(),
+        _ => bail!(
+            "Unknown placeholder {{{}}} in container name (expected {{branch}}, {{dir}}, {{user}} or {{hash}})",
+            placeholder
+        ),
+    })?;
+
+    let name = sanitize(&expanded);
+    if name.is_empty() {
+        bail!(
+            "Container name '{}' expands to '{}', which contains no valid characters",
+            template,
+            expanded
+        );
+    }
+    Ok(name)
+}
+
+/// Replaces every `{placeholder}` in a template with the resolver's value
+fn expand(template: &str, mut resolve: impl FnMut(&str) -> Result<String>) -> Result<String> {
+    let mut expanded = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        expanded.push_str(&rest[..start]);
+        let after = &rest[start + 1..];
+        let end = after
+            .find('}')
+            .with_context(|| format!("Unclosed placeholder in container name: {}", template))?;
+        expanded.push_str(&resolve(&after[..end])?);
+        rest = &after[end + 1..];
+    }
+    expanded.push_str(rest);
+
+    Ok(expanded)
+}
+
+/// Restricts a name to the character set container engines accept
+///
+/// Names must match `[a-zA-Z0-9][a-zA-Z0-9_.-]*`; invalid characters are
+/// replaced with `-` and leading non-alphanumeric characters are dropped.
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || "_.-".contains(c) {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect::<String>()
+        .trim_start_matches(|c: char| !c.is_ascii_alphanumeric())
+        .to_string()
+}
+
+/// Returns the basename of the current directory
+fn current_dir_name() -> Result<String> {
+    let dir = env::current_dir().context("Failed to get current directory")?;
+    dir.file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .context("Cannot resolve {dir}: the current directory has no name")
+}
+
+/// Returns the current user's name
+fn current_user() -> Result<String> {
+    users::get_current_username()
+        .map(|name| name.to_string_lossy().into_owned())
+        .context("Cannot resolve {user}: the current UID has no user name")
+}
+
+/// Returns the git branch checked out in the current directory
+fn git_branch() -> Result<String> {
+    let output = Command::new("git")
+        .arg("branch")
+        .arg("--show-current")
+        .output()
+        .context("Cannot resolve {branch}: failed to run git")?;
+
+    if !output.status.success() {
+        bail!("Cannot resolve {{branch}}: the current directory is not inside a git repository");
+    }
+
+    let branch = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if branch.is_empty() {
+        bail!("Cannot resolve {{branch}}: HEAD is detached");
+    }
+    Ok(branch)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand() {
+        let resolve = |placeholder: &str| match placeholder {
+            "branch" => Ok("feature/login".to_string()),
+            "hash" => Ok("a1b2c3d4e5f6".to_string()),
+            _ => bail!("unknown"),
+        };
+
+        assert_eq!(expand("myproj", resolve).unwrap(), "myproj");
+        assert_eq!(
+            expand("myproj-{branch}", resolve).unwrap(),
+            "myproj-feature/login"
+        );
+        assert_eq!(
+            expand("{branch}-{hash}", resolve).unwrap(),
+            "feature/login-a1b2c3d4e5f6"
+        );
+        assert!(expand("{nope}", resolve).is_err());
+        assert!(expand("myproj-{branch", resolve).is_err());
+    }
+
+    #[test]
+    fn test_sanitize() {
+        assert_eq!(sanitize("myproj-feature/login"), "myproj-feature-login");
+        assert_eq!(sanitize("dev_env.1"), "dev_env.1");
+        assert_eq!(sanitize(".hidden dir"), "hidden-dir");
+        assert_eq!(sanitize("///"), "");
+    }
+
+    #[test]
+    fn test_expand_container_name() {
+        assert_eq!(
+            expand_container_name("env-{hash}", "a1b2c3d4e5f6").unwrap(),
+            "env-a1b2c3d4e5f6"
+        );
+        assert!(expand_container_name("env-{unknown}", "a1b2c3d4e5f6").is_err());
+    }
+}
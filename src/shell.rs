@@ -0,0 +1,117 @@
+//! Shell serialization of engine commands
+//!
+//! This module turns assembled `Command`s back into shell command lines,
+//! quoting arguments so the output can be pasted into a shell or saved as
+//! a script that reproduces the invocation without this tool.
+
+use anyhow::{Context, Result};
+use std::ffi::OsStr;
+use std::fs;
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use std::process::Command;
+
+/// Quotes a single argument for a POSIX shell
+///
+/// Arguments made only of characters that are never special to the shell
+/// are returned as-is; everything else is wrapped in single quotes.
+pub fn quote(arg: &str) -> String {
+    let is_safe = |c: char| c.is_ascii_alphanumeric() || "_@%+=:,./-".contains(c);
+
+    if !arg.is_empty() && arg.chars().all(is_safe) {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', r"'\''"))
+    }
+}
+
+/// Renders a command as a single shell command line
+///
+/// Environment variables set on the command are emitted as `NAME=value`
+/// assignments in front of the program.
+pub fn command_line(cmd: &Command) -> String {
+    let lossy = |s: &OsStr| quote(&s.to_string_lossy());
+
+    let mut parts = Vec::new();
+    for (name, value) in cmd.get_envs() {
+        if let Some(value) = value {
+            parts.push(format!("{}={}", name.to_string_lossy(), lossy(value)));
+        }
+    }
+    parts.push(lossy(cmd.get_program()));
+    parts.extend(cmd.get_args().map(lossy));
+
+    parts.join(" ")
+}
+
+/// Renders commands as a POSIX shell script that stops at the first failure
+pub fn script(commands: &[Command]) -> String {
+    let mut script = String::from("#!/bin/sh\nset -e\n\n");
+    for cmd in commands {
+        script.push_str(&command_line(cmd));
+        script.push('\n');
+    }
+    script
+}
+
+/// Writes commands to an executable shell script
+///
+/// # Arguments
+///
+/// * `path` - Where to write the script
+/// * `commands` - The commands the script should run, in order
+///
+/// # Returns
+///
+/// Returns `Ok(())` on success or an error if the file cannot be written.
+pub fn write_script(path: &Path, commands: &[Command]) -> Result<()> {
+    fs::write(path, script(commands))
+        .with_context(|| format!("Failed to write script: {}", path.display()))?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+        .with_context(|| format!("Failed to make script executable: {}", path.display()))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("podman"), "podman");
+        assert_eq!(quote("/home/user:/home/user"), "/home/user:/home/user");
+        assert_eq!(quote("UID=1000"), "UID=1000");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("my dir"), "'my dir'");
+        assert_eq!(quote("it's"), r"'it'\''s'");
+        assert_eq!(quote("{{.Id}}"), "'{{.Id}}'");
+    }
+
+    #[test]
+    fn test_command_line() {
+        let mut cmd = Command::new("docker");
+        cmd.env("DOCKER_BUILDKIT", "1")
+            .arg("run")
+            .arg("-v")
+            .arg("/my project:/my project")
+            .arg("bash");
+
+        assert_eq!(
+            command_line(&cmd),
+            "DOCKER_BUILDKIT=1 docker run -v '/my project:/my project' bash"
+        );
+    }
+
+    #[test]
+    fn test_script() {
+        let mut start = Command::new("podman");
+        start.arg("start").arg("abc");
+        let mut exec = Command::new("podman");
+        exec.arg("exec").arg("-it").arg("abc").arg("/bin/bash");
+
+        assert_eq!(
+            script(&[start, exec]),
+            "#!/bin/sh\nset -e\n\npodman start abc\npodman exec -it abc /bin/bash\n"
+        );
+    }
+}
@@ -0,0 +1,66 @@
+//! Shared test fixtures
+//!
+//! Temporary directories that are removed when dropped, so fixtures are
+//! cleaned up even when an assertion panics.
+
+use std::env;
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+/// A directory under the system temp dir that is deleted on drop
+pub struct TempDir {
+    path: PathBuf,
+}
+
+impl TempDir {
+    /// Creates an empty directory unique to this test process and `name`
+    pub fn new(name: &str) -> Self {
+        let path = env::temp_dir().join(format!("containers-{}-{}", name, std::process::id()));
+        // Leftovers from a killed run with a recycled pid would break the fixture
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        Self { path }
+    }
+
+    /// Returns the directory path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+/// A Dockerfile in a `dotfiles` directory, symlinked from a `project` directory
+pub struct SymlinkedDockerfile {
+    /// Canonical path of the directory holding the real Dockerfile
+    pub real_dir: PathBuf,
+    /// Path of the symlink in the project directory
+    pub link: PathBuf,
+    /// Keeps both directories alive for the duration of the test
+    _root: TempDir,
+}
+
+impl SymlinkedDockerfile {
+    /// Creates the fixture in a fresh temporary directory named after `name`
+    pub fn new(name: &str) -> Self {
+        let root = TempDir::new(name);
+        let real_dir = root.path().join("dotfiles");
+        let link_dir = root.path().join("project");
+        fs::create_dir_all(&real_dir).unwrap();
+        fs::create_dir_all(&link_dir).unwrap();
+        fs::write(real_dir.join("Dockerfile"), "FROM scratch\n").unwrap();
+        let link = link_dir.join("Dockerfile");
+        symlink(real_dir.join("Dockerfile"), &link).unwrap();
+
+        Self {
+            real_dir: real_dir.canonicalize().unwrap(),
+            link,
+            _root: root,
+        }
+    }
+}